@@ -17,17 +17,27 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>
 
 use anyhow::{Context, Result, bail};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::debug;
 use serialport::SerialPort;
 use std::io::{Read, Write};
 use std::str::FromStr;
 
-fn main() {
+mod protocol;
+
+fn main() -> Result<()> {
     let flags = flags::Rumbac::from_env_or_exit();
 
+    let mut logger = env_logger::Builder::from_default_env();
+    if flags.verbose {
+        logger.filter_level(log::LevelFilter::Trace);
+    }
+    logger.init();
+
     let Some(port) = flags.port else {
         // list known ports
         // TODO: make it prettier
-        let ports = serialport::available_ports().expect("Failed to read serial ports");
+        let ports = serialport::available_ports().context("Failed to read serial ports")?;
         let n = ports.len();
         let ending = match n {
             0 => "s.",
@@ -39,43 +49,85 @@ fn main() {
             println!(
                 "HINT: Did you press the magic combination of button(s) on your plugged-in device to put it in RESET / BOOT mode?"
             );
-            return;
+            return Ok(());
         }
         for p in ports {
             println!(" {:?} = {:?}", p.port_name, p.port_type);
         }
-        return;
+        return Ok(());
     };
 
     println!("Initializing {port:?}...");
-    let (mut port, feats, flash) = init(&port).unwrap();
+
+    if flags.monitor {
+        if flags.file.is_some() {
+            bail!("--monitor cannot be combined with a file to flash");
+        }
+        // Only the version handshake is needed here, not chip
+        // identification: the monitor is exactly the tool you'd reach for
+        // on a chip that fails identify() (e.g. to read its CHIPID/DSU
+        // register and find the mask/value to add to DEVICES).
+        let (mut port, _feats) = connect(&port)?;
+        monitor(&mut port);
+        return Ok(());
+    }
+
+    let (mut port, feats, flash) = init(&port)?;
+
+    if let Some(out) = flags.dump_out {
+        let addr = flags
+            .dump_addr
+            .as_deref()
+            .map(parse_u32)
+            .transpose()
+            .context("Invalid --dump-addr")?
+            .unwrap_or(flash.addr);
+        let len = flags
+            .dump_len
+            .as_deref()
+            .map(parse_u32)
+            .transpose()
+            .context("Invalid --dump-len")?
+            .unwrap_or(flash.pages * flash.size);
+        dump(&mut port, &flash, addr, len, &out)?;
+        return Ok(());
+    }
 
     let Some(file) = flags.file else {
         println!("{feats:?}");
         println!("{flash:?}");
-        return;
+        return Ok(());
     };
-    let mut file = std::fs::File::open(file).expect("Cannot open input file");
-    {
-        let metadata = file.metadata().expect("Cannot retrieve file size");
-        let size = metadata.len();
-        let max_size = flash.pages as u64 * flash.size as u64;
-        if size > max_size {
-            panic!("File size {size} too big, must not exceed flash size {max_size}");
-        }
+    let mut file = std::fs::File::open(file).context("Cannot open input file")?;
+    let size = file.metadata().context("Cannot retrieve file size")?.len();
+    let max_size = flash.pages as u64 * flash.size as u64;
+    if size > max_size {
+        bail!("File size {size} too big, must not exceed flash size {max_size}");
     }
 
     // write file to flash
     if !feats.write_buffer {
-        panic!("only write_buffer flashing method currently implemented");
+        bail!("only write_buffer flashing method currently implemented");
+    }
+    port.write("N#")?;
+    port.expect("\n\r")?;
+
+    if flags.erase {
+        erase(&mut port, &feats, &flash, size as u32)?;
     }
-    port.write("N#");
-    port.expect("\n\r");
+
     const WRITE_BUF_SIZE: u32 = 4096;
     let mut buf = vec![0u8; WRITE_BUF_SIZE as usize];
     let mut offset = 0u32;
+    let pb = ProgressBar::new(size);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, eta {eta})",
+        )
+        .unwrap(),
+    );
     loop {
-        let mut n = read_buf(&mut file, &mut buf).expect("Error reading input file") as u32;
+        let mut n = read_buf(&mut file, &mut buf).context("Error reading input file")? as u32;
         if n == 0 {
             break; // eof
         }
@@ -90,41 +142,199 @@ fn main() {
             }
         }
 
-        port.write(&format!("S{:08X},{n:08X}#", flash.user));
+        port.write(&format!("S{:08X},{n:08X}#", flash.user))?;
         let _ = port.inner.flush();
-        port.write_all(&buf[..n as usize]);
+        port.write_all(&buf[..n as usize])?;
+        port.drain_echo()?;
 
-        port.write(&format!("Y{:08X},0#", flash.user));
-        port.expect("Y\n\r");
+        port.write(&format!("Y{:08X},0#", flash.user))?;
+        port.expect("Y\n\r")?;
 
         let dst_addr = flash.addr + offset;
-        port.write(&format!("Y{dst_addr:08X},{n:08X}#"));
-        port.expect("Y\n\r");
+        port.write(&format!("Y{dst_addr:08X},{n:08X}#"))?;
+        port.expect("Y\n\r")?;
+
+        if flags.verify {
+            verify_region(&mut port, &feats, dst_addr, &buf[..n as usize])?;
+        }
 
         offset += n;
+        pb.set_position((offset as u64).min(size));
     }
-
-    // TODO: verify (if flag set)
+    pb.finish_with_message("done");
 
     if feats.reset {
-        port.write("K#");
+        port.write("K#")?;
     }
+    Ok(())
 }
 
-fn init(port_name: &str) -> Result<(Port, Feats, Flash)> {
-    // TODO: what baudrate to use by default??
-    // let bauds = 921600u32;
-    let bauds = 230400u32;
+/// Erases flash before writing `len` bytes, via the bootloader's full-chip
+/// erase command (`X#`). There is no per-region erase command exposed by
+/// this protocol, and the write-buffer path cannot substitute for one: on
+/// NOR flash a plain write can only clear bits (1->0), never set an
+/// already-programmed zero bit back to 1, so "erasing" by overwriting with
+/// `0xFF` silently does nothing wherever the old contents had zero bits,
+/// and the following real write then programs the OR of old and new data
+/// instead of the intended content.
+fn erase(port: &mut Port, feats: &Feats, flash: &Flash, _len: u32) -> Result<()> {
+    if !feats.chip_erase {
+        bail!(
+            "Device does not advertise chip_erase, and this protocol has no \
+             per-region erase command; refusing to fake one with a write, \
+             since that cannot actually clear a non-blank flash. Flash \
+             a device you know to be blank, or omit --erase."
+        );
+    }
+    // A full chip erase can take several seconds on real flash, far longer
+    // than protocol::Budget::DEFAULT allows for an ordinary exchange.
+    const CHIP_ERASE_BUDGET: protocol::Budget = protocol::Budget {
+        timeout: std::time::Duration::from_secs(10),
+        retries: 2,
+    };
+    port.write(&format!("X{:08X}#", flash.addr))?;
+    port.expect_timeout("X\n\r", CHIP_ERASE_BUDGET)?;
+    println!("Erased entire chip");
+    Ok(())
+}
+
+/// Reads `len` bytes starting at `addr` off the device into `out_path`, the
+/// inverse of flashing. Used to back up existing firmware before reflashing
+/// or to diff against what `--verify` expects.
+fn dump(port: &mut Port, flash: &Flash, addr: u32, len: u32, out_path: &str) -> Result<()> {
+    let mut out = std::fs::File::create(out_path).context("Cannot create dump output file")?;
+    const READ_BUF_SIZE: u32 = 4096;
+    // Keep each R# window a multiple of the flash page size.
+    let window = (READ_BUF_SIZE / flash.size).max(1) * flash.size;
+    let mut offset = 0u32;
+    while offset < len {
+        let n = window.min(len - offset);
+        let data = port.read_block(addr + offset, n)?;
+        out.write_all(&data).context("Failed to write dump output file")?;
+        offset += n;
+    }
+    Ok(())
+}
+
+/// Interactive peek/poke REPL for the SAM-BA protocol, modeled as a simple
+/// command-loop debugger: read a line, split into args, dispatch. Lets
+/// `repeat <n>` re-run the last command, handy for polling a register.
+fn monitor(port: &mut Port) {
+    use std::io::{BufRead, stdin};
+
+    println!("rumbac monitor. Commands: w W o O h H dump id reset repeat help quit");
+    let mut last: Option<Vec<String>> = None;
+    for line in stdin().lock().lines() {
+        let line = line.expect("Failed to read from stdin");
+        let mut args: Vec<&str> = line.split_whitespace().collect();
+        if args.is_empty() {
+            let Some(prev) = &last else { continue };
+            args = prev.iter().map(String::as_str).collect();
+        }
+
+        match args[0] {
+            "quit" | "exit" => break,
+            "help" => println!(
+                "w <addr>          read word\n\
+                 W <addr> <val>    write word\n\
+                 o <addr>          read byte\n\
+                 O <addr> <val>    write byte\n\
+                 h <addr>          read halfword\n\
+                 H <addr> <val>    write halfword\n\
+                 dump <addr> <len> hex-dump memory\n\
+                 id                re-run the identify-chip command\n\
+                 reset             reset the device\n\
+                 repeat <n>        repeat the last command n times\n\
+                 quit              leave the monitor"
+            ),
+            "repeat" => {
+                let Some(prev) = last.clone() else {
+                    println!("No previous command to repeat");
+                    continue;
+                };
+                let count: u32 = args.get(1).and_then(|s| s.parse().ok()).unwrap_or(1);
+                for _ in 0..count {
+                    run_monitor_command(port, &prev.iter().map(String::as_str).collect::<Vec<_>>());
+                }
+                continue;
+            }
+            _ => run_monitor_command(port, &args),
+        }
+
+        last = Some(args.iter().map(|s| s.to_string()).collect());
+    }
+}
+
+fn run_monitor_command(port: &mut Port, args: &[&str]) {
+    let result: Result<()> = (|| {
+        match args {
+            ["w", addr] => println!("{:08X}", port.read_word(parse_u32(addr)?)?),
+            ["W", addr, value] => port.write_word(parse_u32(addr)?, parse_u32(value)?)?,
+            ["o", addr] => println!("{:02X}", port.read_byte(parse_u32(addr)?)?),
+            ["O", addr, value] => port.write_byte(parse_u32(addr)?, parse_u32(value)? as u8)?,
+            ["h", addr] => println!("{:04X}", port.read_halfword(parse_u32(addr)?)?),
+            ["H", addr, value] => port.write_halfword(parse_u32(addr)?, parse_u32(value)? as u16)?,
+            ["dump", addr, len] => {
+                let addr = parse_u32(addr)?;
+                hexdump(addr, &port.read_block(addr, parse_u32(len)?)?);
+            }
+            ["id"] => {
+                port.write("I#")?;
+                println!("{}", port.read_str()?);
+            }
+            ["reset"] => port.write("K#")?,
+            _ => println!("Unrecognized command {args:?}, type 'help' for a list"),
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        println!("error: {e:#}");
+    }
+}
+
+/// Prints `data` (read from `base_addr`) as a classic 16-bytes-per-line hex
+/// dump with an ASCII sidebar.
+fn hexdump(base_addr: u32, data: &[u8]) {
+    for (i, row) in data.chunks(16).enumerate() {
+        let addr = base_addr + (i * 16) as u32;
+        let hex: Vec<String> = row.iter().map(|b| format!("{b:02X}")).collect();
+        let ascii: String = row
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect();
+        println!("{addr:08X}  {:<47}  {ascii}", hex.join(" "));
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number, as accepted by the
+/// `--dump-addr`/`--dump-len` flags.
+fn parse_u32(s: &str) -> Result<u32> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .with_context(|| format!("Cannot parse {s:?} as a number"))
+}
+
+/// Opens `port_name` and performs the version handshake (`V#`), returning
+/// the connected [`Port`] and parsed [`Feats`] without resolving a [`Flash`]
+/// descriptor. Used directly by `--monitor`, which needs neither
+/// `identify()` nor a recognized chip to do its job; [`init`] layers chip
+/// identification on top for the flashing/dump paths.
+fn connect(port_name: &str) -> Result<(Port, Feats)> {
+    // Flow control is now timeout/retry based (see `protocol`), so it's safe
+    // to use the bootloader's fastest rate instead of the conservative one.
+    let bauds = 921_600u32;
     use core::time::Duration;
     let mut port: Port = serialport::new(port_name, bauds)
-        .timeout(Duration::from_secs(1))
+        .timeout(Duration::from_millis(200))
         .open()
         .with_context(|| format!("Failed to open port {port_name}"))?
         .into();
 
     // get "version" info
-    port.write("V#");
-    let version = port.read_str();
+    port.write("V#")?;
+    let version = port.read_str()?;
     // parse "version" info
     const FEATS_PREFIX: &str = "[Arduino:";
     const FEATS_SUFFIX: &str = "]";
@@ -136,33 +346,159 @@ fn init(port_name: &str) -> Result<(Port, Feats, Flash)> {
     let feats_end = feats
         .find(FEATS_SUFFIX)
         .with_context(|| format!("No {FEATS_SUFFIX:?} found in version info {version:?}"))?;
-    let feats: Feats = feats[..feats_end].parse().unwrap();
+    let feats: Feats = feats[..feats_end]
+        .parse()
+        .map_err(|ParseFeatsError(b)| anyhow::anyhow!("Unknown feature flag {b:#04X} in {version:?}"))?;
+
+    Ok((port, feats))
+}
 
+fn init(port_name: &str) -> Result<(Port, Feats, Flash)> {
+    let (mut port, feats) = connect(port_name)?;
+    let flash = identify(&mut port, &feats)
+        .with_context(|| format!("Device at {port_name:?} not recognized"))?;
+    Ok((port, feats, flash))
+}
+
+/// Resolves the chip on the other end of `port` against [`DEVICES`], either by
+/// the `I#` identify-chip reply (when the bootloader supports it) or by
+/// reading its CHIPID/DSU ID register and matching it against a mask/value
+/// pair, and returns the matched [`Flash`] descriptor.
+fn identify(port: &mut Port, feats: &Feats) -> Result<Flash> {
     if feats.identify_chip {
-        port.write("I#");
-        match port.read_str().as_ref() {
-            FAMILY_NRF52 => {
-                return Ok((
-                    port,
-                    feats,
-                    Flash {
-                        name: FAMILY_NRF52.into(),
-                        addr: 0,
-                        pages: 256,
-                        size: 4096,
-                        planes: 1,
-                        lock_regions: 0,
-                        user: 0,
-                        stack: 0,
-                    },
-                ));
-            }
-            _ => (),
+        port.write("I#")?;
+        let reply = port.read_str()?;
+        return DEVICES
+            .iter()
+            .find(|d| d.text == Some(reply.as_str()))
+            .map(|d| d.flash)
+            .with_context(|| format!("Unrecognized chip identity {reply:?}"));
+    }
+
+    for d in DEVICES {
+        let Some(reg) = d.reg else { continue };
+        let id = port.read_word(reg.addr)?;
+        if id & reg.mask == reg.value {
+            return Ok(d.flash);
         }
     }
-    bail!("Device at {port_name:?} not recognized");
+    bail!("No CHIPID/DSU register matched any known chip");
 }
 
+/// A chip the device table knows how to flash, matched either by its `I#`
+/// identity string or by a CHIPID/DSU register mask/value pair.
+struct ChipId {
+    text: Option<&'static str>,
+    reg: Option<ChipIdReg>,
+    flash: Flash,
+}
+
+/// A CHIPID/DSU-style identification register: read `addr`, mask off the
+/// revision/variant bits that don't identify the family, and compare.
+#[derive(Clone, Copy)]
+struct ChipIdReg {
+    addr: u32,
+    mask: u32,
+    value: u32,
+}
+
+/// Known SAM-BA/BOSSA-compatible Cortex-M devices. Flash geometry (page
+/// size/count, lock regions, RAM staging buffer) comes from each chip's
+/// datasheet; `user`/`stack` are the RAM addresses the bootloader expects
+/// a write buffer and stack pointer at, respectively.
+const DEVICES: &[ChipId] = &[
+    ChipId {
+        text: Some(FAMILY_NRF52),
+        reg: None,
+        flash: Flash {
+            name: FAMILY_NRF52,
+            addr: 0,
+            pages: 256,
+            size: 4096,
+            planes: 1,
+            lock_regions: 0,
+            user: 0,
+            stack: 0,
+        },
+    },
+    ChipId {
+        // DSU->DID, ignoring the revision in the low byte.
+        text: None,
+        reg: Some(ChipIdReg {
+            addr: 0x4100_2018,
+            mask: 0xFFFF_FF00,
+            value: 0x1001_0100,
+        }),
+        flash: Flash {
+            name: "SAMD21G18A",
+            addr: 0,
+            pages: 4096,
+            size: 64,
+            planes: 1,
+            lock_regions: 16,
+            user: 0x2000_2000,
+            stack: 0x2000_8000,
+        },
+    },
+    ChipId {
+        // DSU->DID, same register as SAMD21 (shared DSU peripheral).
+        text: None,
+        reg: Some(ChipIdReg {
+            addr: 0x4100_2018,
+            mask: 0xFFFF_FF00,
+            value: 0x6181_0300,
+        }),
+        flash: Flash {
+            name: "SAMD51P20A",
+            addr: 0,
+            pages: 1024,
+            size: 512,
+            planes: 1,
+            lock_regions: 32,
+            user: 0x2000_4000,
+            stack: 0x2003_0000,
+        },
+    },
+    ChipId {
+        // CHIPID_CIDR, ignoring the version bits in the low 5 bits.
+        text: None,
+        reg: Some(ChipIdReg {
+            addr: 0x400E_0740,
+            mask: 0x7FFF_FFE0,
+            value: 0x285E_0A60,
+        }),
+        flash: Flash {
+            name: "SAM3X8E",
+            addr: 0x0008_0000,
+            pages: 2048,
+            size: 256,
+            planes: 2,
+            lock_regions: 16,
+            user: 0x2000_1000,
+            stack: 0x2001_8000,
+        },
+    },
+    ChipId {
+        // CHIPID_CIDR, ignoring the version bits in the low 5 bits.
+        text: None,
+        reg: Some(ChipIdReg {
+            addr: 0x400E_0740,
+            mask: 0x7FFF_FFE0,
+            value: 0x289C_0A60,
+        }),
+        flash: Flash {
+            name: "SAM4S16B",
+            addr: 0x0040_0000,
+            pages: 2048,
+            size: 512,
+            planes: 1,
+            lock_regions: 16,
+            user: 0x2000_1000,
+            stack: 0x2002_0000,
+        },
+    },
+];
+
 struct Port {
     inner: Box<dyn SerialPort>,
 }
@@ -178,64 +514,130 @@ impl Port {
         Self { inner: p }
     }
 
-    pub fn write(&mut self, s: &str) {
-        println!("> {:?}", s);
-        self.write_all(s.as_bytes());
+    pub fn write(&mut self, s: &str) -> Result<()> {
+        debug!("> {:?}", s);
+        self.write_all(s.as_bytes())
     }
 
-    pub fn write_all(&mut self, buf: &[u8]) {
+    pub fn write_all(&mut self, buf: &[u8]) -> Result<()> {
         let mut offset: usize = 0;
         while offset < buf.len() {
             offset += self
                 .inner
                 .write(&buf[offset..])
-                .expect("Failed to write to port");
-            std::thread::sleep(std::time::Duration::from_millis(10));
+                .context("Failed to write to port")?;
         }
+        Ok(())
     }
 
-    pub fn expect(&mut self, response: &str) {
-        let mut buf = vec![b' '; response.len()];
-        let mut offset: usize = 0;
-        while offset < buf.len() {
-            offset += self
-                .inner
-                .read(&mut buf[offset..])
-                .expect("Failed to read from port");
+    /// Drains whatever the bootloader echoes back for a raw buffer write
+    /// before the next command is sent, replacing the old fixed post-write
+    /// delay. Only the call sites that write a raw firmware buffer
+    /// (not textual commands) need this: those are the only writes that
+    /// aren't immediately followed by reading the real reply.
+    pub fn drain_echo(&mut self) -> Result<()> {
+        let mut byte = [0u8; 1];
+        loop {
+            match self.inner.read(&mut byte) {
+                Ok(0) => break,
+                Ok(_) => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => break,
+                Err(e) => return Err(e).context("Failed to drain port echo"),
+            }
         }
-        let line = std::str::from_utf8(&buf).expect("Cannot parse as UTF8");
-        println!("< {line:?}");
+        Ok(())
+    }
+
+    pub fn expect(&mut self, response: &str) -> Result<()> {
+        self.expect_timeout(response, protocol::Budget::DEFAULT)
+    }
+
+    /// Like [`Port::expect`], but with a caller-chosen timeout/retry budget
+    /// instead of [`protocol::Budget::DEFAULT`] — for exchanges (e.g. a full
+    /// chip erase) that can legitimately take far longer to answer than an
+    /// ordinary peek/poke.
+    pub fn expect_timeout(&mut self, response: &str, budget: protocol::Budget) -> Result<()> {
+        let buf = protocol::read_framed(
+            &mut *self.inner,
+            protocol::Framing::Fixed(response.len()),
+            budget,
+        )?;
+        let line = std::str::from_utf8(&buf).context("Cannot parse response as UTF8")?;
+        debug!("< {line:?}");
         if line != response {
-            panic!("got unexpected response");
+            bail!("Got unexpected response {line:?}, expected {response:?}");
         }
+        Ok(())
     }
 
-    pub fn read_str(&mut self) -> String {
-        let mut buf = vec![b' '; 256];
+    pub fn read_str(&mut self) -> Result<String> {
+        let buf = protocol::read_framed(&mut *self.inner, protocol::Framing::Line, protocol::Budget::DEFAULT)?;
+        let line = std::str::from_utf8(&buf).context("Cannot parse response as UTF8")?;
+        debug!("< {line:?}");
+        Ok(line.into())
+    }
 
-        let mut offset: usize = 0;
-        loop {
-            let n = self
-                .inner
-                .read(&mut buf[offset..])
-                .expect("Failed to read from port");
-            if let Some(idx) = buf[offset..offset + n].iter().position(|b| *b == 0) {
-                buf.truncate(offset + idx);
-                break;
-            }
-            offset += n;
-            if offset == buf.len() {
-                panic!("read_str buffer too small");
-            }
-        }
+    /// Issues the checksum_buffer command (`Z#`) and returns the CRC16-CCITT
+    /// the bootloader computed over `[addr, addr+len)`.
+    pub fn checksum(&mut self, addr: u32, len: u32) -> Result<u16> {
+        self.write(&format!("Z{addr:08X},{len:08X}#"))?;
+        let reply = self.read_str()?;
+        let hex = reply
+            .strip_prefix('Z')
+            .and_then(|s| s.strip_suffix('#'))
+            .with_context(|| format!("Unexpected checksum reply {reply:?}"))?;
+        u16::from_str_radix(hex, 16).with_context(|| format!("Invalid checksum reply {reply:?}"))
+    }
 
-        let line = std::str::from_utf8(&buf).expect("Cannot parse as UTF8");
-        println!("< {line:?}");
-        buf.pop_if(|b| *b == b'\0');
-        buf.pop_if(|b| *b == b'\r');
-        buf.pop_if(|b| *b == b'\n');
-        let line = std::str::from_utf8(&buf).expect("Cannot parse as UTF8");
-        line.into()
+    /// Reads `len` bytes starting at `addr` back off the device via the
+    /// receive command (`R#`).
+    pub fn read_block(&mut self, addr: u32, len: u32) -> Result<Vec<u8>> {
+        self.write(&format!("R{addr:08X},{len:08X}#"))?;
+        protocol::read_framed(
+            &mut *self.inner,
+            protocol::Framing::Fixed(len as usize),
+            protocol::Budget::DEFAULT,
+        )
+    }
+
+    /// Reads a 32-bit word at `addr` (`w#`).
+    pub fn read_word(&mut self, addr: u32) -> Result<u32> {
+        self.write(&format!("w{addr:08X},4#"))?;
+        let reply = self.read_str()?;
+        u32::from_str_radix(reply.trim(), 16)
+            .with_context(|| format!("Invalid word-read reply {reply:?} for address {addr:#010X}"))
+    }
+
+    /// Writes a 32-bit word `value` at `addr` (`W#`).
+    pub fn write_word(&mut self, addr: u32, value: u32) -> Result<()> {
+        self.write(&format!("W{addr:08X},{value:08X}#"))
+    }
+
+    /// Reads a byte at `addr` (`o#`).
+    pub fn read_byte(&mut self, addr: u32) -> Result<u8> {
+        self.write(&format!("o{addr:08X},#"))?;
+        let reply = self.read_str()?;
+        u8::from_str_radix(reply.trim(), 16)
+            .with_context(|| format!("Invalid byte-read reply {reply:?} for address {addr:#010X}"))
+    }
+
+    /// Writes a byte `value` at `addr` (`O#`).
+    pub fn write_byte(&mut self, addr: u32, value: u8) -> Result<()> {
+        self.write(&format!("O{addr:08X},{value:02X}#"))
+    }
+
+    /// Reads a halfword at `addr` (`h#`).
+    pub fn read_halfword(&mut self, addr: u32) -> Result<u16> {
+        self.write(&format!("h{addr:08X},#"))?;
+        let reply = self.read_str()?;
+        u16::from_str_radix(reply.trim(), 16).with_context(|| {
+            format!("Invalid halfword-read reply {reply:?} for address {addr:#010X}")
+        })
+    }
+
+    /// Writes a halfword `value` at `addr` (`H#`).
+    pub fn write_halfword(&mut self, addr: u32, value: u16) -> Result<()> {
+        self.write(&format!("H{addr:08X},{value:04X}#"))
     }
 }
 
@@ -272,9 +674,9 @@ impl FromStr for Feats {
 
 const FAMILY_NRF52: &str = "nRF52840-QIAA";
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct Flash {
-    name: String,
+    name: &'static str,
     addr: u32,
     pages: u32,
     size: u32, // page size
@@ -284,6 +686,61 @@ struct Flash {
     stack: u32,
 }
 
+/// Verifies that `data`, just written at `dst_addr`, actually landed on the
+/// device: via the checksum_buffer command if the bootloader supports it,
+/// falling back to a byte-for-byte read-back otherwise. Aborts with a
+/// per-region diagnostic on any mismatch.
+fn verify_region(port: &mut Port, feats: &Feats, dst_addr: u32, data: &[u8]) -> Result<()> {
+    let len = data.len() as u32;
+    if feats.checksum_buffer {
+        let expected = crc16_ccitt(data);
+        let actual = port.checksum(dst_addr, len)?;
+        if actual != expected {
+            bail!(
+                "Verification failed for region {dst_addr:#010X}..{:#010X}: expected CRC {expected:04X}, got {actual:04X}",
+                dst_addr + len
+            );
+        }
+    } else {
+        let read_back = port.read_block(dst_addr, len)?;
+        if read_back != data {
+            bail!(
+                "Verification failed for region {dst_addr:#010X}..{:#010X}: content mismatch",
+                dst_addr + len
+            );
+        }
+    }
+    Ok(())
+}
+
+/// CRC16-CCITT/XMODEM, as computed by the bootloader's checksum_buffer
+/// command: no input/output reflection, no final XOR.
+fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crc16_ccitt;
+
+    #[test]
+    fn crc16_ccitt_known_vector() {
+        // Standard CRC-16/XMODEM check value for the ASCII string "123456789".
+        assert_eq!(crc16_ccitt(b"123456789"), 0x31C3);
+    }
+}
+
 fn read_buf(r: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
     let mut off = 0usize;
     while off < buf.len() {
@@ -316,8 +773,18 @@ mod flags {
         cmd rumbac {
             optional port: String
             optional file: String
+            // Verify the flashed data against the device afterwards
+            optional --verify
+            // Read memory back into a file instead of flashing
+            optional --dump-addr addr: String
+            optional --dump-len len: String
+            optional --dump-out path: String
+            // Interactive peek/poke monitor instead of flashing
+            optional -m,--monitor
+            // Log every wire exchange, even without RUST_LOG set
+            optional -v,--verbose
             // Erase the flash - may speed up writing
-            // optional -e,--erase
+            optional -e,--erase
         }
     }
     // generated start
@@ -327,6 +794,13 @@ mod flags {
     pub struct Rumbac {
         pub port: Option<String>,
         pub file: Option<String>,
+        pub verify: bool,
+        pub dump_addr: Option<String>,
+        pub dump_len: Option<String>,
+        pub dump_out: Option<String>,
+        pub monitor: bool,
+        pub verbose: bool,
+        pub erase: bool,
     }
 
     impl Rumbac {
@@ -0,0 +1,126 @@
+// Typed request/response framing for the SAM-BA serial protocol.
+//
+// `Port` funnels every exchange through `read_framed` so timeout and retry
+// behavior live in one place instead of being duplicated (and panicking) at
+// each call site.
+
+use anyhow::{Context, Result, bail};
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// How a command's response is delimited on the wire.
+#[derive(Debug, Clone, Copy)]
+pub enum Framing {
+    /// Reply terminated by a NUL byte, with a trailing `\r`/`\n` (not
+    /// included in the returned bytes).
+    Line,
+    /// A fixed number of raw bytes with no delimiter (fixed acknowledgements
+    /// and `R#` block reads).
+    Fixed(usize),
+}
+
+/// Per-command timeout and retry allowance. Most exchanges are fine with
+/// [`Budget::DEFAULT`], but some (e.g. a full chip erase) genuinely take
+/// much longer to answer than an ordinary peek/poke, so callers can pass
+/// their own.
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    /// How long to wait for more bytes before treating the response as
+    /// stalled.
+    pub timeout: Duration,
+    /// Number of times a stalled response is given a fresh `timeout` window
+    /// before giving up. There's no way to resend the originating command
+    /// from here, so a "retry" extends the deadline rather than discarding
+    /// bytes already received: a device that's just slow still gets its
+    /// partial frame completed instead of the stream being desynced by
+    /// throwing it away.
+    pub retries: u32,
+}
+
+impl Budget {
+    pub const DEFAULT: Budget = Budget {
+        timeout: Duration::from_millis(500),
+        retries: 2,
+    };
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Reads a single framed response from `port`. Bytes already received are
+/// never discarded: if the response stalls mid-frame, `budget.retries`
+/// further `budget.timeout` windows are granted before giving up, rather
+/// than restarting from an empty buffer.
+pub fn read_framed(port: &mut dyn Read, framing: Framing, budget: Budget) -> Result<Vec<u8>> {
+    match framing {
+        Framing::Fixed(len) => read_fixed(port, len, budget),
+        Framing::Line => read_line(port, budget),
+    }
+}
+
+fn read_fixed(port: &mut dyn Read, len: usize, budget: Budget) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    let mut offset = 0;
+    let mut attempt = 0;
+    let mut deadline = Instant::now() + budget.timeout;
+    while offset < len {
+        if Instant::now() > deadline {
+            attempt += 1;
+            if attempt > budget.retries {
+                bail!("Timed out waiting for a {len}-byte response ({offset} bytes received)");
+            }
+            deadline = Instant::now() + budget.timeout;
+            continue;
+        }
+        match port.read(&mut buf[offset..]) {
+            Ok(0) => continue,
+            Ok(n) => {
+                offset += n;
+                deadline = Instant::now() + budget.timeout;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e).context("Failed to read from port"),
+        }
+    }
+    Ok(buf)
+}
+
+fn read_line(port: &mut dyn Read, budget: Budget) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut byte = [0u8; 1];
+    let mut attempt = 0;
+    let mut deadline = Instant::now() + budget.timeout;
+    loop {
+        if Instant::now() > deadline {
+            attempt += 1;
+            if attempt > budget.retries {
+                bail!(
+                    "Timed out waiting for a line response ({} bytes received)",
+                    buf.len()
+                );
+            }
+            deadline = Instant::now() + budget.timeout;
+            continue;
+        }
+        match port.read(&mut byte) {
+            Ok(0) => continue,
+            Ok(_) if byte[0] == 0 => break,
+            Ok(_) => {
+                buf.push(byte[0]);
+                deadline = Instant::now() + budget.timeout;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e).context("Failed to read from port"),
+        }
+    }
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    }
+    Ok(buf)
+}